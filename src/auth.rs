@@ -0,0 +1,51 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{Request, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+
+use crate::state::SharedState;
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Compare in constant time so a timing side-channel can't be used to guess
+/// the token one byte at a time; plain `==` on `&str` short-circuits on the
+/// first mismatching byte.
+fn tokens_match(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Guards every route in the `protected` router. Accepts the bearer token
+/// either as an `Authorization: Bearer <token>` header or a `?token=` query
+/// param, since browser clients (e.g. the `/events` `EventSource` feed) can't
+/// attach custom headers to the request. Replaces
+/// `tower_http::validate_request::ValidateRequestHeaderLayer::bearer`, which
+/// is deprecated upstream and compares the header with a plain `==`.
+pub async fn require_token(
+    State(state): State<SharedState>,
+    Query(query): Query<TokenQuery>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let header_token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let provided = header_token.or(query.token.as_deref());
+
+    match provided {
+        Some(token) if tokens_match(token, &state.token) => Ok(next.run(request).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::state::Rule;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -6,6 +7,15 @@ use std::path::PathBuf;
 pub struct Config {
     pub port: u16,
     pub agents_dir: String,
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+}
+
+fn default_allowed_origins() -> Vec<String> {
+    vec![
+        "http://127.0.0.1".to_string(),
+        "http://localhost".to_string(),
+    ]
 }
 
 impl Default for Config {
@@ -13,6 +23,7 @@ impl Default for Config {
         Self {
             port: 58421,
             agents_dir: "./agents".to_string(),
+            allowed_origins: default_allowed_origins(),
         }
     }
 }
@@ -44,3 +55,102 @@ pub fn load_config() -> Config {
         config
     }
 }
+
+const TOKEN_BYTES: usize = 32;
+
+fn generate_token() -> String {
+    use rand::Rng;
+    let bytes: [u8; TOKEN_BYTES] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Load the bearer token persisted at `config_dir/token`, generating and
+/// saving a fresh one (with `0600` permissions) the first time the Brain starts.
+pub fn load_or_create_token() -> String {
+    let path = get_config_dir().join("token");
+    if let Some(token) = read_token() {
+        return token;
+    }
+
+    let token = generate_token();
+    if let Err(e) = write_token_file(&path, &token) {
+        eprintln!("[CONFIG] Failed to write auth token: {}", e);
+    }
+    token
+}
+
+/// Create the token file with `0600` permissions from the start, rather than
+/// writing it world-readable and tightening permissions afterward.
+#[cfg(unix)]
+fn write_token_file(path: &std::path::Path, token: &str) -> std::io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_token_file(path: &std::path::Path, token: &str) -> std::io::Result<()> {
+    fs::write(path, token)
+}
+
+/// Read the previously persisted bearer token, if any (used by the CLI client
+/// and native-messaging host to authenticate against a running Brain).
+pub fn read_token() -> Option<String> {
+    let path = get_config_dir().join("token");
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+/// Load the auto-injection rules persisted at `config_dir/rules.toml`, or an
+/// empty set if none have been saved yet.
+pub fn load_rules() -> Vec<Rule> {
+    let path = get_config_dir().join("rules.toml");
+    if !path.exists() {
+        return Vec::new();
+    }
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("[CONFIG] Error reading rules.toml: {}. Using no rules.", e);
+            return Vec::new();
+        }
+    };
+    toml::from_str::<RulesFile>(&content)
+        .unwrap_or_else(|e| {
+            eprintln!("[CONFIG] Error parsing rules.toml: {}. Using no rules.", e);
+            RulesFile::default()
+        })
+        .rules
+}
+
+/// Persist the current rule set to `config_dir/rules.toml`, overwriting it.
+pub fn save_rules(rules: &[Rule]) {
+    let path = get_config_dir().join("rules.toml");
+    let file = RulesFile {
+        rules: rules.to_vec(),
+    };
+    match toml::to_string_pretty(&file) {
+        Ok(content) => {
+            if let Err(e) = fs::write(&path, content) {
+                eprintln!("[CONFIG] Failed to write rules.toml: {}", e);
+            }
+        }
+        Err(e) => eprintln!("[CONFIG] Failed to serialize rules: {}", e),
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+pub fn open(config_dir: &Path) -> sled::Result<sled::Db> {
+    sled::open(config_dir.join("db"))
+}
+
+fn results_tree(db: &sled::Db) -> sled::Result<sled::Tree> {
+    db.open_tree("results")
+}
+
+fn blobs_tree(db: &sled::Db) -> sled::Result<sled::Tree> {
+    db.open_tree("blobs")
+}
+
+/// Append a captured result, returning the monotonically increasing key it was stored under.
+pub fn append_result(db: &sled::Db, value: &serde_json::Value) -> sled::Result<u64> {
+    let tree = results_tree(db)?;
+    let id = db.generate_id()?;
+    let bytes = serde_json::to_vec(value).expect("result is always valid JSON");
+    tree.insert(id.to_be_bytes(), bytes)?;
+    Ok(id)
+}
+
+/// Scan the results tree newest-first, optionally starting strictly before `before`.
+pub fn recent_results(
+    db: &sled::Db,
+    limit: usize,
+    before: Option<u64>,
+) -> sled::Result<Vec<serde_json::Value>> {
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+    let tree = results_tree(db)?;
+    let upper = before.unwrap_or(u64::MAX).to_be_bytes();
+    let mut out = Vec::with_capacity(limit);
+    for entry in tree.range(..upper).rev() {
+        let (_, bytes) = entry?;
+        if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+            out.push(value);
+        }
+        if out.len() >= limit {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// Store a screenshot (or other large) blob, returning the id it was stored
+/// under. Keyed by `db.generate_id()` (durable and monotonic across restarts),
+/// never by the in-process request-id counter, which resets to 1 every `Start`
+/// and would silently collide with blobs from a previous session.
+pub fn store_blob(db: &sled::Db, data: &[u8]) -> sled::Result<u64> {
+    let id = db.generate_id()?;
+    blobs_tree(db)?.insert(id.to_be_bytes(), data)?;
+    Ok(id)
+}
+
+pub fn fetch_blob(db: &sled::Db, blob_id: u64) -> sled::Result<Option<Vec<u8>>> {
+    Ok(blobs_tree(db)?.get(blob_id.to_be_bytes())?.map(|v| v.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    #[test]
+    fn recent_results_with_limit_zero_returns_nothing() {
+        let db = test_db();
+        append_result(&db, &serde_json::json!({"n": 1})).unwrap();
+        assert!(recent_results(&db, 0, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn recent_results_are_newest_first_and_capped_at_limit() {
+        let db = test_db();
+        append_result(&db, &serde_json::json!({"n": 1})).unwrap();
+        append_result(&db, &serde_json::json!({"n": 2})).unwrap();
+        append_result(&db, &serde_json::json!({"n": 3})).unwrap();
+
+        let results = recent_results(&db, 2, None).unwrap();
+        assert_eq!(
+            results,
+            vec![serde_json::json!({"n": 3}), serde_json::json!({"n": 2})]
+        );
+    }
+
+    #[test]
+    fn store_and_fetch_blob_round_trips() {
+        let db = test_db();
+        let id = store_blob(&db, b"screenshot bytes").unwrap();
+        assert_eq!(fetch_blob(&db, id).unwrap(), Some(b"screenshot bytes".to_vec()));
+    }
+
+    #[test]
+    fn fetch_blob_with_unknown_id_returns_none() {
+        let db = test_db();
+        assert_eq!(fetch_blob(&db, 999).unwrap(), None);
+    }
+
+    #[test]
+    fn blob_ids_stay_unique_across_calls() {
+        let db = test_db();
+        let a = store_blob(&db, b"first").unwrap();
+        let b = store_blob(&db, b"second").unwrap();
+        assert_ne!(a, b);
+    }
+}
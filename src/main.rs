@@ -1,22 +1,38 @@
+mod auth;
+mod config;
+mod db;
+mod protocol;
 mod state;
 
 use axum::{
     Json, Router,
     extract::{
-        State,
+        Path, Query, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    response::IntoResponse,
-    routing::{get, post},
+    http::{HeaderValue, Method, StatusCode},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get, post},
 };
 use clap::{Parser, Subcommand};
-use futures_util::{sink::SinkExt, stream::StreamExt};
-use state::{AppState, InjectRequest, SharedState};
+use futures_util::{Stream, sink::SinkExt, stream::StreamExt};
+use serde::Deserialize;
+use state::{AppState, BrainCommand, CaptureRequest, ClickRequest, InjectRequest, Rule, SharedState};
+use std::convert::Infallible;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
 use tower_http::cors::CorsLayer;
 
 const DEFAULT_PORT: u16 = 58421;
+const DISPATCH_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
 #[command(name = "bgm-controller")]
@@ -56,16 +72,78 @@ enum Commands {
         #[arg(short, long, default_value = "active")]
         tab: String,
     },
+    /// Run as a Chrome/Firefox native-messaging host over stdio
+    NativeHost,
+    /// Manage auto-injection rules (run a script whenever a tab's URL matches a pattern)
+    Rule {
+        #[command(subcommand)]
+        action: RuleCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum RuleCommand {
+    /// Add a rule matching `pattern` (a glob, e.g. "https://example.com/*") that injects `script`
+    Add {
+        pattern: String,
+        script: String,
+        #[arg(short, long, default_value_t = true)]
+        enabled: bool,
+    },
+    /// List all rules
+    List,
+    /// Remove a rule by id
+    Remove { id: String },
+}
+
+#[derive(Deserialize)]
+struct WaitQuery {
+    wait: Option<bool>,
+}
+
+/// Mint a request id, stamp it onto `fields` as `id`/`type`, broadcast the
+/// message to the extension, and (when `wait` is set) block until the
+/// matching `*_result` is routed back by `handle_socket`, or the dispatch
+/// times out.
+async fn dispatch_and_respond(
+    state: &SharedState,
+    msg_type: &str,
+    mut fields: serde_json::Value,
+    wait: bool,
+) -> Json<serde_json::Value> {
+    let id = state.next_request_id.fetch_add(1, Ordering::SeqCst);
+    fields["type"] = serde_json::json!(msg_type);
+    fields["id"] = serde_json::json!(id);
+
+    let rx = wait.then(|| {
+        let (tx, rx) = oneshot::channel();
+        state.pending.lock().unwrap().insert(id, tx);
+        rx
+    });
+
+    let _ = state.tx.send(fields);
+
+    let Some(rx) = rx else {
+        return Json(serde_json::json!({ "status": "sent", "id": id }));
+    };
+
+    match tokio::time::timeout(DISPATCH_TIMEOUT, rx).await {
+        Ok(Ok(result)) => Json(result),
+        Ok(Err(_)) => Json(serde_json::json!({ "status": "error", "id": id, "error": "sender dropped" })),
+        Err(_) => {
+            state.pending.lock().unwrap().remove(&id);
+            Json(serde_json::json!({ "status": "timeout", "id": id }))
+        }
+    }
 }
 
 async fn inject_handler(
     State(state): State<SharedState>,
+    Query(q): Query<WaitQuery>,
     Json(payload): Json<InjectRequest>,
 ) -> Json<serde_json::Value> {
-    let msg =
-        serde_json::json!({ "type": "inject", "tabId": payload.tab_id, "script": payload.script });
-    let _ = state.tx.send(msg);
-    Json(serde_json::json!({ "status": "sent" }))
+    let fields = serde_json::json!({ "tabId": payload.tab_id, "script": payload.script });
+    dispatch_and_respond(&state, "inject", fields, q.wait.unwrap_or(false)).await
 }
 
 async fn get_tabs_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
@@ -73,26 +151,199 @@ async fn get_tabs_handler(State(state): State<SharedState>) -> Json<serde_json::
     Json(serde_json::json!({ "tabs": *tabs }))
 }
 
-async fn navigate_handler(
+async fn click_handler(
     State(state): State<SharedState>,
-    Json(payload): Json<serde_json::Value>,
+    Json(payload): Json<ClickRequest>,
 ) -> Json<serde_json::Value> {
-    let msg = serde_json::json!({
-        "type": "navigate",
-        "tabId": payload.get("tab_id").unwrap_or(&serde_json::json!("active")),
-        "url": payload.get("url").unwrap_or(&serde_json::json!("https://google.com"))
-    });
-    let _ = state.tx.send(msg);
-    Json(serde_json::json!({ "status": "navigation_sent" }))
+    let fields = serde_json::json!({ "tabId": payload.tab_id, "selector": payload.selector });
+    dispatch_and_respond(&state, "click", fields, true).await
 }
 
-async fn results_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
-    let results = state.results.lock().unwrap();
-    Json(serde_json::json!({ "results": *results }))
+async fn capture_handler(
+    State(state): State<SharedState>,
+    Json(payload): Json<CaptureRequest>,
+) -> Json<serde_json::Value> {
+    let fields = serde_json::json!({ "tabId": payload.tab_id });
+    dispatch_and_respond(&state, "capture", fields, true).await
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<SharedState>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+/// Single validated entry point for Navigate, OpenTab, Tabs and Results (the
+/// last two are also served by `GET /tabs` and `GET /results`). Click/Capture
+/// live at `/click`/`/capture` and Inject at `/inject`, so `BrainCommand`
+/// doesn't duplicate them here.
+async fn command_handler(
+    State(state): State<SharedState>,
+    Query(q): Query<WaitQuery>,
+    Json(cmd): Json<BrainCommand>,
+) -> Json<serde_json::Value> {
+    let wait = q.wait.unwrap_or(false);
+    match cmd {
+        BrainCommand::Navigate { tab_id, url } => {
+            let fields = serde_json::json!({ "tabId": tab_id, "url": url });
+            dispatch_and_respond(&state, "navigate", fields, wait).await
+        }
+        BrainCommand::OpenTab { url } => {
+            let fields = serde_json::json!({
+                "tabId": "new",
+                "url": url.unwrap_or_else(|| "about:blank".to_string())
+            });
+            dispatch_and_respond(&state, "navigate", fields, wait).await
+        }
+        BrainCommand::Tabs => {
+            let tabs = state.tabs.lock().unwrap();
+            Json(serde_json::json!({ "tabs": *tabs }))
+        }
+        BrainCommand::Results => match db::recent_results(&state.db, 50, None) {
+            Ok(results) => Json(serde_json::json!({ "results": results })),
+            Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+        },
+    }
+}
+
+#[derive(Deserialize)]
+struct ResultsQuery {
+    limit: Option<usize>,
+    before: Option<u64>,
+}
+
+async fn results_handler(
+    State(state): State<SharedState>,
+    Query(q): Query<ResultsQuery>,
+) -> Json<serde_json::Value> {
+    let limit = q.limit.unwrap_or(50).min(500);
+    match db::recent_results(&state.db, limit, q.before) {
+        Ok(results) => Json(serde_json::json!({ "results": results })),
+        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
+async fn result_blob_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match db::fetch_blob(&state.db, id) {
+        Ok(Some(bytes)) => (axum::http::StatusCode::OK, bytes).into_response(),
+        Ok(None) => (axum::http::StatusCode::NOT_FOUND, "no blob with that id").into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )
+            .into_response(),
+    }
+}
+
+async fn list_rules_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    let rules = state.rules.lock().unwrap();
+    Json(serde_json::json!({ "rules": *rules }))
+}
+
+#[derive(Deserialize)]
+struct NewRule {
+    pattern: String,
+    script: String,
+    #[serde(default = "default_rule_enabled")]
+    enabled: bool,
+}
+
+fn default_rule_enabled() -> bool {
+    true
+}
+
+async fn add_rule_handler(
+    State(state): State<SharedState>,
+    Json(payload): Json<NewRule>,
+) -> Json<serde_json::Value> {
+    let id = state.next_rule_id.fetch_add(1, Ordering::SeqCst);
+    let rule = Rule {
+        id: format!("rule-{}", id),
+        pattern: payload.pattern,
+        script: payload.script,
+        enabled: payload.enabled,
+    };
+
+    let mut rules = state.rules.lock().unwrap();
+    rules.push(rule.clone());
+    config::save_rules(&rules);
+
+    Json(serde_json::json!({ "status": "added", "rule": rule }))
+}
+
+async fn delete_rule_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let mut rules = state.rules.lock().unwrap();
+    let before = rules.len();
+    rules.retain(|r| r.id != id);
+    let removed = rules.len() != before;
+    config::save_rules(&rules);
+
+    Json(serde_json::json!({ "status": if removed { "removed" } else { "not_found" }, "id": id }))
+}
+
+/// Scripts from every enabled rule whose glob `pattern` matches `url`.
+fn matching_scripts(rules: &[Rule], url: &str) -> Vec<String> {
+    rules
+        .iter()
+        .filter(|r| r.enabled)
+        .filter(|r| {
+            glob::Pattern::new(&r.pattern)
+                .map(|p| p.matches(url))
+                .unwrap_or(false)
+        })
+        .map(|r| r.script.clone())
+        .collect()
+}
+
+/// Derive the next rule id from the highest `rule-N` id among the loaded
+/// rules, not from the loaded rule count, so a new id never collides with one
+/// still in use. Ids of deleted rules are not remembered: after the rule with
+/// the highest id is deleted, that id can be issued again on the next `Start`.
+fn next_rule_id(rules: &[Rule]) -> u64 {
+    rules
+        .iter()
+        .filter_map(|r| r.id.strip_prefix("rule-")?.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+async fn health_handler() -> &'static str {
+    "ok"
+}
+
+#[derive(Deserialize)]
+struct WsAuthQuery {
+    token: Option<String>,
+}
+
+/// `/ws` lives outside the `protected` router (rather than under
+/// `auth::require_token`) and checks the token itself, rejecting the upgrade
+/// before the socket is accepted; it reads the same bearer token only from a
+/// `?token=` query param, since a browser WebSocket constructor can't carry an
+/// `Authorization` header.
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<SharedState>,
+    Query(auth): Query<WsAuthQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if auth.token.as_deref() != Some(state.token.as_str()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(ws.on_upgrade(|socket| handle_socket(socket, state)))
+}
+
+/// Read-only feed of tab-list changes and captured results for dashboards and
+/// scripts that don't want to speak the bidirectional `/ws` protocol.
+async fn events_handler(
+    State(state): State<SharedState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.events_tx.subscribe();
+    let stream = BroadcastStream::new(rx).filter_map(|msg| async move {
+        let msg = msg.ok()?;
+        Some(Ok(Event::default().json_data(msg).unwrap_or_default()))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 async fn handle_socket(socket: WebSocket, state: SharedState) {
@@ -120,18 +371,70 @@ async fn handle_socket(socket: WebSocket, state: SharedState) {
                                 if let Ok(new_tabs) =
                                     serde_json::from_value::<Vec<state::TabInfo>>(tabs_val.clone())
                                 {
-                                    let mut tabs = state_inner.tabs.lock().unwrap();
-                                    *tabs = new_tabs;
+                                    {
+                                        let mut tabs = state_inner.tabs.lock().unwrap();
+                                        *tabs = new_tabs.clone();
+                                    }
                                     eprintln!("[BGM] Updated Tab List");
+                                    let _ = state_inner.events_tx.send(serde_json::json!({
+                                        "type": "tabs_updated",
+                                        "tabs": new_tabs
+                                    }));
+
+                                    let rules = state_inner.rules.lock().unwrap();
+                                    for tab in &new_tabs {
+                                        if let Some(url) = &tab.url {
+                                            for script in matching_scripts(&rules, url) {
+                                                let _ = state_inner.tx.send(serde_json::json!({
+                                                    "type": "inject",
+                                                    "tabId": tab.id,
+                                                    "script": script
+                                                }));
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        "navigation_complete" => {
+                            let tab_id = msg.get("tabId").cloned().unwrap_or(serde_json::Value::Null);
+                            if let Some(url) = msg.get("url").and_then(|u| u.as_str()) {
+                                let rules = state_inner.rules.lock().unwrap();
+                                for script in matching_scripts(&rules, url) {
+                                    let _ = state_inner.tx.send(serde_json::json!({
+                                        "type": "inject",
+                                        "tabId": tab_id,
+                                        "script": script
+                                    }));
                                 }
                             }
                         }
                         "injection_result" | "html_result" | "capture_result" => {
-                            let mut results = state_inner.results.lock().unwrap();
-                            results.push(msg.clone());
-                            if results.len() > 100 {
-                                results.remove(0);
+                            let request_id = msg.get("id").and_then(|v| v.as_u64());
+                            if let Some(id) = request_id {
+                                if let Some(sender) = state_inner.pending.lock().unwrap().remove(&id) {
+                                    let _ = sender.send(msg.clone());
+                                }
+                            }
+
+                            // Screenshots are large; keep them out of the JSON history and
+                            // fetch them separately through `/results/:id/blob`. Blob ids come
+                            // from `db.generate_id()`, not the request-id counter, since that
+                            // counter resets to 1 on every restart while the blob tree persists.
+                            let mut entry = msg.clone();
+                            if msg_type == "capture_result" {
+                                if let Some(data) = entry.get("data").and_then(|d| d.as_str()) {
+                                    if let Ok(blob_id) = db::store_blob(&state_inner.db, data.as_bytes()) {
+                                        entry["data"] = serde_json::Value::Null;
+                                        entry["blobId"] = serde_json::json!(blob_id);
+                                    }
+                                }
                             }
+
+                            if let Err(e) = db::append_result(&state_inner.db, &entry) {
+                                eprintln!("[BGM] Failed to persist result: {}", e);
+                            }
+                            let _ = state_inner.events_tx.send(msg.clone());
                             eprintln!("[BGM] Captured result: {}", msg_type);
                         }
                         _ => {}
@@ -148,6 +451,73 @@ async fn handle_socket(socket: WebSocket, state: SharedState) {
     eprintln!("[BGM] Extension Disconnected");
 }
 
+/// Explicit CORS allow-list driven by `Config::allowed_origins`, replacing the
+/// permissive default that let any local page drive the browser.
+fn build_cors_layer(config: &config::Config) -> CorsLayer {
+    let origins: Vec<HeaderValue> = config
+        .allowed_origins
+        .iter()
+        .filter_map(|o| HeaderValue::from_str(o).ok())
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            axum::http::header::AUTHORIZATION,
+            axum::http::header::CONTENT_TYPE,
+        ])
+}
+
+/// Bridge stdin/stdout to the Brain's WebSocket endpoint using the
+/// length-prefixed native-messaging framing the browser speaks.
+async fn run_native_host() -> Result<(), Box<dyn std::error::Error>> {
+    let token = config::read_token().unwrap_or_default();
+    let url = format!("ws://127.0.0.1:{}/ws?token={}", DEFAULT_PORT, token);
+    let (ws_stream, _) = tokio_tungstenite::connect_async(&url).await?;
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+
+    let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+
+    let stdin_task = tokio::task::spawn_blocking(move || {
+        let stdin = io::stdin();
+        let mut locked = stdin.lock();
+        // Loop ends on EOF (the browser closed the pipe) or on the first frame that fails to
+        // read or parse as JSON, including a zero-length one.
+        while let Ok(msg) = protocol::read_message(&mut locked) {
+            if stdin_tx.send(msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut send_task = tokio::spawn(async move {
+        while let Some(msg) = stdin_rx.recv().await {
+            if ws_write.send(WsMessage::Text(msg.to_string())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut recv_task = tokio::spawn(async move {
+        let mut stdout = io::stdout();
+        while let Some(Ok(WsMessage::Text(text))) = ws_read.next().await {
+            if let Ok(val) = serde_json::from_str::<serde_json::Value>(&text) {
+                if protocol::write_message(&mut stdout, &val).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    };
+    stdin_task.abort();
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
@@ -167,55 +537,114 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 std::process::exit(1);
             }
 
+            let app_config = config::load_config();
+            let rules = config::load_rules();
+            let next_rule_id_val = next_rule_id(&rules);
+            let db = db::open(&config::get_config_dir()).expect("Failed to open results database");
+            let token = config::load_or_create_token();
+            eprintln!(
+                "[BGM BRAIN] Auth token ready at {}/token (send it as `Authorization: Bearer <token>`)",
+                config::get_config_dir().display()
+            );
+
             let (tx, _) = broadcast::channel::<serde_json::Value>(100);
+            let (events_tx, _) = broadcast::channel::<serde_json::Value>(100);
             let state = Arc::new(AppState {
                 tx,
+                events_tx,
                 tabs: Mutex::new(Vec::new()),
-                results: Mutex::new(Vec::new()),
+                db,
+                next_request_id: AtomicU64::new(1),
+                pending: Mutex::new(std::collections::HashMap::new()),
+                rules: Mutex::new(rules),
+                next_rule_id: AtomicU64::new(next_rule_id_val),
+                token: token.clone(),
             });
 
-            let app = Router::new()
+            let protected = Router::new()
                 .route("/inject", post(inject_handler))
                 .route("/tabs", get(get_tabs_handler))
-                .route("/navigate", post(navigate_handler))
+                .route("/command", post(command_handler))
+                .route("/click", post(click_handler))
+                .route("/capture", post(capture_handler))
                 .route("/results", get(results_handler))
+                .route("/results/{id}/blob", get(result_blob_handler))
+                .route("/rules", get(list_rules_handler).post(add_rule_handler))
+                .route("/rules/{id}", delete(delete_rule_handler))
+                .route("/events", get(events_handler))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    Arc::clone(&state),
+                    auth::require_token,
+                ));
+
+            let app = Router::new()
+                .route("/health", get(health_handler))
                 .route("/ws", get(ws_handler))
-                .layer(CorsLayer::permissive())
+                .merge(protected)
+                .layer(build_cors_layer(&app_config))
                 .with_state(Arc::clone(&state));
 
             let listener = tokio::net::TcpListener::bind(&addr_str).await?;
             eprintln!("[BGM BRAIN] Operating on http://{}", addr_str);
             axum::serve(listener, app).await?;
         }
+        Commands::NativeHost => {
+            run_native_host().await?;
+        }
         _ => {
             let client = reqwest::Client::new();
             let base_url = format!("http://127.0.0.1:{}", DEFAULT_PORT);
+            let token = config::read_token().unwrap_or_default();
 
             let result = match cli.command {
                 Commands::Navigate { url, tab } => {
-                    client.post(format!("{}/navigate", base_url))
-                        .json(&serde_json::json!({ "url": url, "tab_id": tab }))
+                    client.post(format!("{}/command", base_url))
+                        .bearer_auth(&token)
+                        .json(&serde_json::json!({ "type": "navigate", "tabId": tab, "url": url }))
                         .send().await?.json::<serde_json::Value>().await?
                 }
                 Commands::OpenTab { url } => {
-                    client.post(format!("{}/navigate", base_url))
-                        .json(&serde_json::json!({ "url": url.unwrap_or("about:blank".to_string()), "tab_id": "new" }))
+                    client.post(format!("{}/command", base_url))
+                        .bearer_auth(&token)
+                        .json(&serde_json::json!({ "type": "open_tab", "url": url }))
                         .send().await?.json::<serde_json::Value>().await?
                 }
                 Commands::Tabs => {
-                    client.get(format!("{}/tabs", base_url)).send().await?
+                    client.get(format!("{}/tabs", base_url)).bearer_auth(&token).send().await?
                         .json::<serde_json::Value>().await?
                 }
                 Commands::Results => {
-                    client.get(format!("{}/results", base_url)).send().await?
+                    client.get(format!("{}/results", base_url)).bearer_auth(&token).send().await?
                         .json::<serde_json::Value>().await?
                 }
                 Commands::Click { selector, tab } => {
-                    // Reuse navigate handler structure since common bridge handles many types
-                    client.post(format!("{}/navigate", base_url)) // Note: In a real system you'd have more specific endpoints
-                        .json(&serde_json::json!({ "type": "click", "selector": selector, "tab_id": tab }))
+                    client.post(format!("{}/click", base_url))
+                        .bearer_auth(&token)
+                        .json(&serde_json::json!({ "tabId": tab, "selector": selector }))
+                        .send().await?.json::<serde_json::Value>().await?
+                }
+                Commands::Capture { tab } => {
+                    client.post(format!("{}/capture", base_url))
+                        .bearer_auth(&token)
+                        .json(&serde_json::json!({ "tabId": tab }))
                         .send().await?.json::<serde_json::Value>().await?
                 }
+                Commands::Rule { action } => match action {
+                    RuleCommand::Add { pattern, script, enabled } => {
+                        client.post(format!("{}/rules", base_url))
+                            .bearer_auth(&token)
+                            .json(&serde_json::json!({ "pattern": pattern, "script": script, "enabled": enabled }))
+                            .send().await?.json::<serde_json::Value>().await?
+                    }
+                    RuleCommand::List => {
+                        client.get(format!("{}/rules", base_url)).bearer_auth(&token).send().await?
+                            .json::<serde_json::Value>().await?
+                    }
+                    RuleCommand::Remove { id } => {
+                        client.delete(format!("{}/rules/{}", base_url, id)).bearer_auth(&token).send().await?
+                            .json::<serde_json::Value>().await?
+                    }
+                },
                 _ => serde_json::json!({ "error": "Command dispatcher not fully implemented" })
             };
 
@@ -224,3 +653,59 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, pattern: &str, script: &str, enabled: bool) -> Rule {
+        Rule {
+            id: id.to_string(),
+            pattern: pattern.to_string(),
+            script: script.to_string(),
+            enabled,
+        }
+    }
+
+    #[test]
+    fn next_rule_id_starts_at_one_when_empty() {
+        assert_eq!(next_rule_id(&[]), 1);
+    }
+
+    #[test]
+    fn next_rule_id_continues_past_max_existing() {
+        let rules = vec![
+            rule("rule-1", "*", "", true),
+            rule("rule-5", "*", "", true),
+            rule("rule-3", "*", "", true),
+        ];
+        assert_eq!(next_rule_id(&rules), 6);
+    }
+
+    #[test]
+    fn next_rule_id_follows_highest_remaining_id_after_a_deletion() {
+        // rule-5 has since been deleted and is not remembered; the next add gets 4
+        // (the highest remaining id plus one), not 3 (the count-based id, taken by rule-3).
+        let rules = vec![rule("rule-1", "*", "", true), rule("rule-3", "*", "", true)];
+        assert_eq!(next_rule_id(&rules), 4);
+    }
+
+    #[test]
+    fn matching_scripts_skips_disabled_and_non_matching_rules() {
+        let rules = vec![
+            rule("rule-1", "https://example.com/*", "a", true),
+            rule("rule-2", "https://example.com/*", "b", false),
+            rule("rule-3", "https://other.com/*", "c", true),
+        ];
+        assert_eq!(
+            matching_scripts(&rules, "https://example.com/page"),
+            vec!["a".to_string()]
+        );
+    }
+
+    #[test]
+    fn matching_scripts_ignores_an_invalid_glob_pattern() {
+        let rules = vec![rule("rule-1", "[", "a", true)];
+        assert!(matching_scripts(&rules, "https://example.com/").is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, oneshot};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InjectRequest {
@@ -24,10 +26,60 @@ pub struct Rule {
     pub enabled: bool,
 }
 
+fn default_tab_id() -> serde_json::Value {
+    serde_json::json!("active")
+}
+
+/// Validated body for `POST /command`, tagged by its `type` field.
+///
+/// Click and Capture are deliberately not variants here: they have their own
+/// dedicated `/click`/`/capture` routes (see `ClickRequest`/`CaptureRequest`
+/// below), and Inject already has `/inject`. Tabs and Results are variants
+/// even though `GET /tabs` and `GET /results` also serve them.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrainCommand {
+    Navigate {
+        #[serde(rename = "tabId", default = "default_tab_id")]
+        tab_id: serde_json::Value,
+        url: String,
+    },
+    OpenTab {
+        url: Option<String>,
+    },
+    Tabs,
+    Results,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClickRequest {
+    #[serde(rename = "tabId", default = "default_tab_id")]
+    pub tab_id: serde_json::Value,
+    pub selector: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptureRequest {
+    #[serde(rename = "tabId", default = "default_tab_id")]
+    pub tab_id: serde_json::Value,
+}
+
 pub struct AppState {
-    pub tx: mpsc::UnboundedSender<serde_json::Value>,
+    pub tx: broadcast::Sender<serde_json::Value>,
+    /// Read-only feed of tab-list changes and captured results, consumed by `/events`.
+    pub events_tx: broadcast::Sender<serde_json::Value>,
     pub tabs: Mutex<Vec<TabInfo>>,
-    pub results: Mutex<Vec<serde_json::Value>>,
+    /// Durable, unbounded result history (sled-backed, see `db.rs`); survives Brain restarts.
+    pub db: sled::Db,
+    /// Mints the `id` embedded in every dispatched command so its eventual
+    /// `*_result` can be routed back to the caller instead of just logged.
+    pub next_request_id: AtomicU64,
+    pub pending: Mutex<HashMap<u64, oneshot::Sender<serde_json::Value>>>,
+    pub rules: Mutex<Vec<Rule>>,
+    pub next_rule_id: AtomicU64,
+    /// Bearer token guarding every route except `/health`; checked manually on
+    /// the `/ws` upgrade since browsers can't set an `Authorization` header.
+    pub token: String,
 }
 
 pub type SharedState = Arc<AppState>;